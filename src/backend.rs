@@ -0,0 +1,233 @@
+use crate::idlemap::IdleMap;
+use crate::kpageflags::KPageFlags;
+use crate::pagemap::{Pagemap, TrackingMode};
+use crate::scanner::{PermClass, Scanner};
+use crate::softdirty::SoftDirty;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// C code uses 0xffff880000000000LLU.
+// In Rust, we can just use a high address check.
+// User space usually ends much lower.
+const PAGE_OFFSET: u64 = 0xffff880000000000;
+
+/// Per-`PermClass` byte accumulator, used to break the working set down
+/// by memory-region permission class in the final report.
+#[derive(Debug, Default, Clone, Copy)]
+struct ClassTotals {
+    active_bytes: u64,
+}
+
+/// Result of a working-set measurement, independent of which backend
+/// produced it. `file_bytes`/`anon_bytes`/`class_bytes` are `None` for
+/// backends (like `SmapsBackend`) that can't break the total down any
+/// further.
+pub struct WssReport {
+    pub metric_label: &'static str,
+    pub est_seconds: f64,
+    pub total_bytes: u64,
+    pub file_bytes: Option<u64>,
+    pub anon_bytes: Option<u64>,
+    pub class_bytes: Option<[u64; PermClass::COUNT]>,
+}
+
+/// A way of estimating a process's working set over an interval. Lets
+/// `main` dispatch on `--method` without caring how each one gets its
+/// numbers.
+pub trait WssBackend {
+    fn measure(&self, pid: i32, duration: f64) -> io::Result<WssReport>;
+}
+
+/// Estimates the working set via the idle-page bitmap (referenced pages)
+/// or, with `dirty` set, via the soft-dirty PTE bit (written pages).
+/// Needs `CONFIG_IDLE_PAGE_TRACKING` in the `!dirty` case.
+pub struct PagemapBackend {
+    pub dirty: bool,
+}
+
+impl WssBackend for PagemapBackend {
+    fn measure(&self, pid: i32, duration: f64) -> io::Result<WssReport> {
+        let metric_label = if self.dirty { "Write" } else { "Ref" };
+
+        // 1. Reset tracking: soft-dirty bits in dirty mode, the idle-page
+        // bitmap otherwise.
+        let ts1 = Instant::now();
+        if self.dirty {
+            SoftDirty::reset(pid)?;
+        } else {
+            IdleMap::set_idlemap()?;
+        }
+        let set_duration = ts1.elapsed();
+
+        // 2. Sleep
+        thread::sleep(Duration::from_secs_f64(duration));
+        let ts3 = Instant::now(); // Time after sleep
+
+        // 3. Read idle flags (skipped in dirty mode: the soft-dirty bit is
+        // read directly from the same pagemap snapshot used for the region
+        // walk). In C code: loadidlemap();
+        let idle_map = if self.dirty {
+            None
+        } else {
+            Some(IdleMap::load()?)
+        };
+        let mode = match &idle_map {
+            Some(map) => TrackingMode::Idle(map),
+            None => TrackingMode::Dirty,
+        };
+
+        // 4. Walk maps
+        let scanner = Scanner::new(pid);
+        let regions = scanner.get_maps()?;
+        let pagemap = Pagemap::new(pid)?;
+        // THP accounting is a refinement, not a prerequisite: /proc/kpageflags
+        // is typically root-only (mode 0440), so a denied open here should
+        // degrade to flat 4 KiB accounting rather than aborting a
+        // measurement that would otherwise succeed.
+        let kpageflags = match KPageFlags::new() {
+            Ok(k) => Some(k),
+            Err(e) => {
+                eprintln!(
+                    "Warning: failed to open /proc/kpageflags ({}), falling back to flat 4 KiB accounting",
+                    e
+                );
+                None
+            }
+        };
+
+        let mut total_active_bytes = 0u64;
+        let mut total_active_file_bytes = 0u64;
+        let mut total_active_anon_bytes = 0u64;
+        let mut class_totals = [ClassTotals::default(); PermClass::COUNT];
+
+        for region in regions {
+            if region.start > PAGE_OFFSET {
+                continue;
+            }
+
+            match pagemap.process_region(
+                region.start,
+                region.end,
+                &mode,
+                region.class,
+                kpageflags.as_ref(),
+            ) {
+                Ok((class, active_file, active_anon, _walked)) => {
+                    let active = active_file + active_anon;
+                    total_active_bytes += active;
+                    total_active_file_bytes += active_file;
+                    total_active_anon_bytes += active_anon;
+                    class_totals[class.index()].active_bytes += active;
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Error processing region {:x}-{:x}: {}",
+                        region.start, region.end, e
+                    );
+                }
+            }
+        }
+
+        let ts4 = Instant::now();
+
+        // Calculate times
+        // C code: est_us = dur_us - (set_us / 2) - (read_us / 2);
+        // dur_us = ts4 - ts1
+        // set_us = ts2 - ts1 (we didn't measure ts2 explicitly but set_duration covers it)
+        // read_us = ts4 - ts3 (includes loadidlemap + walkmaps)
+        let total_duration = ts4.duration_since(ts1);
+        let read_walk_duration = ts4.duration_since(ts3);
+
+        // Estimated duration calculation from C code logic
+        // est = total - (set / 2) - (read_walk / 2)
+        let est_micros = total_duration.as_micros() as i64
+            - (set_duration.as_micros() as i64 / 2)
+            - (read_walk_duration.as_micros() as i64 / 2);
+        let est_seconds = est_micros as f64 / 1_000_000.0;
+
+        let mut class_bytes = [0u64; PermClass::COUNT];
+        for (i, totals) in class_totals.iter().enumerate() {
+            class_bytes[i] = totals.active_bytes;
+        }
+
+        Ok(WssReport {
+            metric_label,
+            est_seconds,
+            total_bytes: total_active_bytes,
+            file_bytes: Some(total_active_file_bytes),
+            anon_bytes: Some(total_active_anon_bytes),
+            class_bytes: Some(class_bytes),
+        })
+    }
+}
+
+/// Estimates the working set via the older reference-bit mechanism: clear
+/// the referenced bit on all PTEs via `clear_refs`, sleep, then sum the
+/// `Referenced:` field across every VMA in `/proc/PID/smaps`. Needs
+/// neither the idle-page bitmap nor a raw pagemap walk, so it works on
+/// stock kernels without `CONFIG_IDLE_PAGE_TRACKING`.
+pub struct SmapsBackend;
+
+impl WssBackend for SmapsBackend {
+    fn measure(&self, pid: i32, duration: f64) -> io::Result<WssReport> {
+        let ts1 = Instant::now();
+        clear_refs(pid)?;
+        let set_duration = ts1.elapsed();
+
+        thread::sleep(Duration::from_secs_f64(duration));
+        let ts3 = Instant::now();
+
+        let total_bytes = referenced_bytes(pid)?;
+        let ts4 = Instant::now();
+
+        let total_duration = ts4.duration_since(ts1);
+        let read_duration = ts4.duration_since(ts3);
+        let est_micros = total_duration.as_micros() as i64
+            - (set_duration.as_micros() as i64 / 2)
+            - (read_duration.as_micros() as i64 / 2);
+        let est_seconds = est_micros as f64 / 1_000_000.0;
+
+        Ok(WssReport {
+            metric_label: "Ref",
+            est_seconds,
+            total_bytes,
+            file_bytes: None,
+            anon_bytes: None,
+            class_bytes: None,
+        })
+    }
+}
+
+/// Clears the referenced bit on all of `pid`'s PTEs by writing `"1"` to
+/// `/proc/PID/clear_refs`.
+fn clear_refs(pid: i32) -> io::Result<()> {
+    let path = format!("/proc/{}/clear_refs", pid);
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    file.write_all(b"1")
+}
+
+/// Sums the `Referenced:` field (reported in kB) across every VMA in
+/// `/proc/PID/smaps`.
+fn referenced_bytes(pid: i32) -> io::Result<u64> {
+    let path = format!("/proc/{}/smaps", pid);
+    let file = File::open(path)?;
+    let reader = BufReader::new(file);
+
+    let mut total_kb = 0u64;
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(rest) = line.strip_prefix("Referenced:") {
+            let kb = rest
+                .trim()
+                .trim_end_matches("kB")
+                .trim()
+                .parse::<u64>()
+                .unwrap_or(0);
+            total_kb += kb;
+        }
+    }
+
+    Ok(total_kb * 1024)
+}
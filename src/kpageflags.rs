@@ -0,0 +1,37 @@
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+
+/// This PFN is the head of a compound (multi-page) allocation, e.g. a huge page.
+pub const KPF_COMPOUND_HEAD: u64 = 1 << 15;
+/// This PFN is a tail sub-page of a compound allocation; already accounted
+/// for via its head.
+pub const KPF_COMPOUND_TAIL: u64 = 1 << 16;
+/// This PFN backs a hugetlbfs huge page.
+pub const KPF_HUGE: u64 = 1 << 17;
+/// This PFN backs a transparent huge page.
+pub const KPF_THP: u64 = 1 << 22;
+
+const KPAGEFLAGS_PATH: &str = "/proc/kpageflags";
+const ENTRY_SIZE: u64 = 8;
+
+/// Looks up per-PFN flags from `/proc/kpageflags`, one 8-byte entry per page
+/// frame. Used to tell transparent/huge pages apart from regular 4 KiB
+/// pages so the working-set byte count isn't undercounted.
+pub struct KPageFlags {
+    file: File,
+}
+
+impl KPageFlags {
+    pub fn new() -> io::Result<Self> {
+        let file = File::open(KPAGEFLAGS_PATH)?;
+        Ok(KPageFlags { file })
+    }
+
+    /// Reads the flags word for a given page frame number.
+    pub fn flags_for_pfn(&self, pfn: u64) -> io::Result<u64> {
+        let mut buf = [0u8; ENTRY_SIZE as usize];
+        self.file.read_exact_at(&mut buf, pfn * ENTRY_SIZE)?;
+        Ok(u64::from_ne_bytes(buf))
+    }
+}
@@ -1,20 +1,26 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use std::process;
-use std::thread;
-use std::time::{Duration, Instant};
 
+mod backend;
 mod idlemap;
+mod kpageflags;
 mod pagemap;
 mod scanner;
-
-use idlemap::IdleMap;
-use pagemap::Pagemap;
-use scanner::Scanner;
-
-// C code uses 0xffff880000000000LLU.
-// In Rust, we can just use a high address check.
-// User space usually ends much lower.
-const PAGE_OFFSET: u64 = 0xffff880000000000;
+mod softdirty;
+
+use backend::{PagemapBackend, SmapsBackend, WssBackend, WssReport};
+use scanner::PermClass;
+
+/// Which mechanism to use for estimating the working set.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Method {
+    /// Idle-page bitmap (or soft-dirty with --dirty). Without --dirty,
+    /// needs CONFIG_IDLE_PAGE_TRACKING.
+    Idle,
+    /// clear_refs + /proc/PID/smaps `Referenced:` fields. Works on stock
+    /// kernels.
+    Refs,
+}
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -24,6 +30,16 @@ struct Args {
 
     /// Duration in seconds to measure
     duration: f64,
+
+    /// Track written pages via the soft-dirty PTE bit instead of
+    /// referenced pages via the idle-page bitmap (only applies to
+    /// --method idle)
+    #[arg(long)]
+    dirty: bool,
+
+    /// Backend used to estimate the working set
+    #[arg(long, value_enum, default_value_t = Method::Idle)]
+    method: Method,
 }
 
 fn main() {
@@ -34,98 +50,70 @@ fn main() {
         process::exit(1);
     }
 
-    println!(
-        "Watching PID {} page references during {:.2} seconds...",
-        args.pid, args.duration
-    );
-
-    // 1. Set idle flags
-    let ts1 = Instant::now();
-    if let Err(e) = IdleMap::set_idlemap() {
-        eprintln!("Failed to set idlemap: {}", e);
-        // Continue? C code exits on some errors but not all.
-        // If we can't write idlemap, we can't reset tracking.
+    if args.dirty && args.method == Method::Refs {
+        eprintln!("--dirty is not supported with --method refs. Exiting.");
         process::exit(1);
     }
-    let set_duration = ts1.elapsed();
 
-    // 2. Sleep
-    let sleep_duration = Duration::from_secs_f64(args.duration);
-    thread::sleep(sleep_duration);
-    let ts3 = Instant::now(); // Time after sleep
+    println!(
+        "Watching PID {} page {} during {:.2} seconds...",
+        args.pid,
+        if args.dirty { "writes" } else { "references" },
+        args.duration
+    );
 
-    // 3. Read idle flags
-    // In C code: loadidlemap();
-    let idle_map = match IdleMap::load() {
-        Ok(map) => map,
-        Err(e) => {
-            eprintln!("Failed to load idlemap: {}", e);
-            process::exit(1);
-        }
+    let backend: Box<dyn WssBackend> = match args.method {
+        Method::Idle => Box::new(PagemapBackend { dirty: args.dirty }),
+        Method::Refs => Box::new(SmapsBackend),
     };
 
-    // 4. Walk maps
-    let scanner = Scanner::new(args.pid);
-    let regions = match scanner.get_maps() {
+    let report = match backend.measure(args.pid, args.duration) {
         Ok(r) => r,
         Err(e) => {
-            eprintln!("Failed to read maps for PID {}: {}", args.pid, e);
-            process::exit(1);
-        }
-    };
-
-    let mut pagemap = match Pagemap::new(args.pid) {
-        Ok(p) => p,
-        Err(e) => {
-            eprintln!("Failed to open pagemap for PID {}: {}", args.pid, e);
+            eprintln!("Failed to measure working set for PID {}: {}", args.pid, e);
             process::exit(1);
         }
     };
 
-    let mut total_active_pages = 0;
-    let mut _total_walked_pages = 0;
+    print_report(&report);
+}
 
-    for region in regions {
-        if region.start > PAGE_OFFSET {
-            continue;
+fn print_report(report: &WssReport) {
+    let ref_mb = report.total_bytes as f64 / (1024.0 * 1024.0);
+    let metric_col = format!("{}(MB)", report.metric_label);
+
+    match (report.file_bytes, report.anon_bytes) {
+        (Some(file_bytes), Some(anon_bytes)) => {
+            let cache_mb = file_bytes as f64 / (1024.0 * 1024.0);
+            let anon_mb = anon_bytes as f64 / (1024.0 * 1024.0);
+            println!(
+                "{:<7} {:>10} {:>10} {:>10}",
+                "Est(s)", metric_col, "Cache(MB)", "Anon(MB)"
+            );
+            println!(
+                "{:<7.3} {:>10.2} {:>10.2} {:>10.2}",
+                report.est_seconds, ref_mb, cache_mb, anon_mb
+            );
         }
-
-        match pagemap.process_region(region.start, region.end, &idle_map) {
-            Ok((active, walked)) => {
-                total_active_pages += active;
-                _total_walked_pages += walked;
-            }
-            Err(e) => {
-                eprintln!(
-                    "Error processing region {:x}-{:x}: {}",
-                    region.start, region.end, e
-                );
-            }
+        _ => {
+            println!("{:<7} {:>10}", "Est(s)", metric_col);
+            println!("{:<7.3} {:>10.2}", report.est_seconds, ref_mb);
         }
     }
 
-    let ts4 = Instant::now();
-
-    // Calculate times
-    // C code: est_us = dur_us - (set_us / 2) - (read_us / 2);
-    // dur_us = ts4 - ts1
-    // set_us = ts2 - ts1 (we didn't measure ts2 explicitly but set_duration covers it)
-    // read_us = ts4 - ts3 (includes loadidlemap + walkmaps)
-
-    let total_duration = ts4.duration_since(ts1);
-    let read_walk_duration = ts4.duration_since(ts3);
-
-    // Estimated duration calculation from C code logic
-    // est = total - (set / 2) - (read_walk / 2)
-    let est_micros = total_duration.as_micros() as i64
-        - (set_duration.as_micros() as i64 / 2)
-        - (read_walk_duration.as_micros() as i64 / 2);
-
-    let est_seconds = est_micros as f64 / 1_000_000.0;
-
-    let page_size = 4096; // 4KB
-    let ref_mb = (total_active_pages as f64 * page_size as f64) / (1024.0 * 1024.0);
-
-    println!("{:<7} {:>10}", "Est(s)", "Ref(MB)");
-    println!("{:<7.3} {:>10.2}", est_seconds, ref_mb);
+    if let Some(class_bytes) = report.class_bytes {
+        println!();
+        println!("{:<8} {:>10}", "CLASS", metric_col);
+        for class in [
+            PermClass::Exec,
+            PermClass::RoData,
+            PermClass::RwData,
+            PermClass::Shared,
+            PermClass::Other,
+        ] {
+            let mb = class_bytes[class.index()] as f64 / (1024.0 * 1024.0);
+            println!("{:<8} {:>10.2}", class.label(), mb);
+        }
+        println!("{:<8} {:>10.2}", "TOTAL", ref_mb);
+    }
 }
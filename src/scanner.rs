@@ -1,10 +1,71 @@
 use std::fs::File;
 use std::io::{self, BufRead, BufReader};
 
+/// Coarse memory-region permission class, derived from the `rwxp` field
+/// of `/proc/PID/maps`. Mirrors the W^X split (executable vs. read-only
+/// vs. writable data) plus a bucket for shared mappings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermClass {
+    /// `r-x` — executable code.
+    Exec,
+    /// `r--` — read-only data.
+    RoData,
+    /// `rw-` — private read-write data.
+    RwData,
+    /// Any mapping with the shared flag (`*s*`) set.
+    Shared,
+    /// Anything that doesn't fit the above (e.g. `---p` guard pages).
+    Other,
+}
+
+impl PermClass {
+    /// Classifies a raw `rwxp`/`rwxs` permission string.
+    pub fn from_perms(perms: &str) -> Self {
+        let bytes = perms.as_bytes();
+        if bytes.len() < 4 {
+            return PermClass::Other;
+        }
+        if bytes[3] == b's' {
+            return PermClass::Shared;
+        }
+        match &perms[0..3] {
+            "r-x" => PermClass::Exec,
+            "r--" => PermClass::RoData,
+            "rw-" => PermClass::RwData,
+            _ => PermClass::Other,
+        }
+    }
+
+    /// Stable index for per-class accumulators (0..=COUNT-1).
+    pub fn index(&self) -> usize {
+        match self {
+            PermClass::Exec => 0,
+            PermClass::RoData => 1,
+            PermClass::RwData => 2,
+            PermClass::Shared => 3,
+            PermClass::Other => 4,
+        }
+    }
+
+    /// Number of distinct classes, for sizing accumulator arrays.
+    pub const COUNT: usize = 5;
+
+    /// Short label used in the report table.
+    pub fn label(&self) -> &'static str {
+        match self {
+            PermClass::Exec => "CODE",
+            PermClass::RoData => "RODATA",
+            PermClass::RwData => "DATA",
+            PermClass::Shared => "SHARED",
+            PermClass::Other => "OTHER",
+        }
+    }
+}
+
 pub struct MemoryRegion {
     pub start: u64,
     pub end: u64,
-    pub _perms: String,
+    pub class: PermClass,
     pub _pathname: String,
 }
 
@@ -52,13 +113,13 @@ fn parse_map_line(line: &str) -> Option<MemoryRegion> {
 
     let start = u64::from_str_radix(range_parts[0], 16).ok()?;
     let end = u64::from_str_radix(range_parts[1], 16).ok()?;
-    let perms = parts.get(1).unwrap_or(&"").to_string();
     let pathname = parts.get(5).unwrap_or(&"").to_string();
+    let class = PermClass::from_perms(parts.get(1).unwrap_or(&""));
 
     Some(MemoryRegion {
         start,
         end,
-        _perms: perms,
+        class,
         _pathname: pathname,
     })
 }
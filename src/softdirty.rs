@@ -0,0 +1,21 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+
+/// Resets the soft-dirty PTE bit for every page in a process, the write-
+/// tracking analog of `IdleMap::set_idlemap`. Writing the ASCII string
+/// `"4"` to `/proc/PID/clear_refs` clears the soft-dirty bit on all of the
+/// process's PTEs; any page written to afterwards has the bit set again,
+/// and that can be read back from bit 55 of its `/proc/PID/pagemap` entry.
+pub struct SoftDirty;
+
+impl SoftDirty {
+    /// Clears the soft-dirty bit on all of `pid`'s PTEs. Must be called
+    /// once before sleeping so the pagemap read after the interval only
+    /// reflects pages written during it.
+    pub fn reset(pid: i32) -> io::Result<()> {
+        let path = format!("/proc/{}/clear_refs", pid);
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        file.write_all(b"4")?;
+        Ok(())
+    }
+}